@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
 
-#[derive(Serialize, Deserialize)]
+use crate::connection;
+
+const EPSILON: f32 = 1e-4;
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Config {
     pub ticks_per_cm: f32,
 
@@ -19,6 +23,9 @@ pub struct Config {
     pub reverse: bool,
     pub reverse_enc: bool,
     pub reverse_enc2: bool,
+
+    // How many times a framed message is re-sent after a NAK or a read timeout.
+    pub retries: u8,
 }
 
 // Read the config file
@@ -42,6 +49,7 @@ pub fn read_config() -> Result<Config, Box<dyn std::error::Error>> {
             reverse_enc2: false,
             reverse: false,
             imu_weight: 1.0,
+            retries: connection::DEFAULT_RETRIES,
         };
 
         let config_str = toml::to_string(&default_config)?;
@@ -81,6 +89,7 @@ pub fn config_command(
     reverse_enc: Option<bool>,
     reverse_enc2: Option<bool>,
     imu_weight: Option<f32>,
+    retries: Option<u8>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut config = read_config()?;
     if let Some(v) = ticks_per_cm {
@@ -122,6 +131,9 @@ pub fn config_command(
     if let Some(v) = reverse_enc2 {
         config.reverse_enc2 = v;
     }
+    if let Some(v) = retries {
+        config.retries = v;
+    }
 
     save_config(&config)?;
 
@@ -139,6 +151,57 @@ pub fn config_command(
     println!("reverse_enc: {}", config.reverse_enc);
     println!("reverse_enc2: {}", config.reverse_enc2);
     println!("imu_weight: {}", config.imu_weight);
+    println!("retries: {}", config.retries);
+
+    Ok(())
+}
+
+// Diff the robot's live config against the local config.toml. With `write`, overwrite the
+// local file with the robot's values.
+pub fn pull_command(write: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = read_config()?;
+    let remote = connection::read_config_from_robot(config.retries)?;
+
+    println!("{:<20} {:>12} {:>12}", "field", "local", "robot");
+    print_diff("kp_move", config.kp_move, remote.kp_turn);
+    print_diff("kp_hold", config.kp_hold, remote.kp_hold);
+    print_diff("kp_straight", config.kp_straight, remote.kp_straight);
+    print_diff("kp_velocity", config.kp_velocity, remote.kp_velocity);
+    print_diff("dowel_off", config.dowel_off, remote.dowel_off);
+    print_diff(
+        "turn_accel_time",
+        config.turn_accel_time,
+        remote.turn_accel_time,
+    );
+    print_diff(
+        "straight_accel_time",
+        config.straight_accel_time,
+        remote.straight_accel_time,
+    );
+    print_diff("friction", config.friction, remote.friction);
+
+    if write {
+        config.kp_move = remote.kp_turn;
+        config.kp_hold = remote.kp_hold;
+        config.kp_straight = remote.kp_straight;
+        config.kp_velocity = remote.kp_velocity;
+        config.dowel_off = remote.dowel_off;
+        config.turn_accel_time = remote.turn_accel_time;
+        config.straight_accel_time = remote.straight_accel_time;
+        config.friction = remote.friction;
+
+        save_config(&config)?;
+        println!("\nWrote robot config to local config.toml.");
+    }
 
     Ok(())
 }
+
+fn print_diff(field: &str, local: f32, robot: f32) {
+    let marker = if (local - robot).abs() > EPSILON {
+        "<-- mismatch"
+    } else {
+        ""
+    };
+    println!("{:<20} {:>12} {:>12} {}", field, local, robot, marker);
+}
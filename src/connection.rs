@@ -1,6 +1,7 @@
-use super::read_config;
+use super::{read_config, Config};
 use std::io::Read;
 use std::mem;
+use std::path::PathBuf;
 use std::slice;
 use std::time::Duration;
 
@@ -43,6 +44,190 @@ pub enum CommandType {
     Transmit,
     TurnMove,
     ReadConfig,
+    Flash,
+    ReadTelemetry,
+}
+
+// Framing: [0xAA start][u8 type][u16 len][payload][u16 crc]
+const FRAME_START: u8 = 0xAA;
+// Frame type used for the raw ConfigCommand payload, which has no command_type byte of its own.
+const FRAME_TYPE_CONFIG: u8 = 0xFF;
+
+const STATUS_ACK: u8 = 0x06;
+const STATUS_NAK_CRC: u8 = 0x15;
+const STATUS_NAK_SEQ: u8 = 0x16;
+
+// Default for Config::retries: how many times a frame is re-sent after a NAK or a read
+// timeout before giving up.
+pub(crate) const DEFAULT_RETRIES: u8 = 3;
+
+// Firmware update sub-protocol, used once the robot has been switched into bootloader mode
+// by a CommandType::Flash command.
+const FRAME_TYPE_FLASH_ERASE: u8 = 0xF0;
+const FRAME_TYPE_FLASH_BLOCK: u8 = 0xF1;
+const FRAME_TYPE_FLASH_FINALIZE: u8 = 0xF2;
+
+// Frame type used for a single Telemetry record streamed back after a run.
+const FRAME_TYPE_TELEMETRY: u8 = 0xFE;
+
+const FLASH_BLOCK_SIZE: usize = 256;
+// Erasing flash can take much longer than a normal ack, so give it more time than the port's
+// regular read timeout.
+const FLASH_ERASE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// CRC-16/CCITT-FALSE: poly 0x1021, init 0xFFFF, no reflection, MSB-first.
+fn crc16_ccitt_false(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            if crc & 0x8000 != 0 {
+                crc = (crc << 1) ^ 0x1021;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+    crc
+}
+
+// CRC-32 (IEEE 802.3), used to verify the whole firmware image on finalize.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFFFFFF;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xEDB88320;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    !crc
+}
+
+// Send `payload` as a framed, CRC-checked packet and wait for the robot's one-byte status,
+// re-sending the same frame on NAK or a read timeout up to `retries` times.
+fn send_frame(
+    port: &mut Box<dyn SerialPort>,
+    frame_type: u8,
+    payload: &[u8],
+    retries: u8,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut crc_data = Vec::with_capacity(1 + 2 + payload.len());
+    crc_data.push(frame_type);
+    crc_data.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    crc_data.extend_from_slice(payload);
+    let crc = crc16_ccitt_false(&crc_data);
+
+    let mut frame = Vec::with_capacity(1 + crc_data.len() + 2);
+    frame.push(FRAME_START);
+    frame.extend_from_slice(&crc_data);
+    frame.extend_from_slice(&crc.to_be_bytes());
+
+    for attempt in 0..=retries {
+        port.write_all(&frame)?;
+        port.flush()?;
+
+        let mut status = [0; 1];
+        match port.read_exact(&mut status) {
+            Ok(()) if status[0] == STATUS_ACK => return Ok(()),
+            // A NAK and an unrecognized status byte are both just noise on the line; either
+            // way the robot didn't ack, so retry the same way a timeout does.
+            Ok(()) => {
+                if attempt == retries {
+                    return Err(format!(
+                        "Robot rejected frame (status 0x{:02X}) after {} attempts",
+                        status[0],
+                        retries + 1
+                    )
+                    .into());
+                }
+            }
+            Err(_) if attempt == retries => {
+                return Err(format!("Timed out waiting for ack after {} attempts", retries + 1).into());
+            }
+            Err(_) => {}
+        }
+    }
+
+    Err("Failed to deliver frame to robot".into())
+}
+
+// Read a framed, CRC-checked packet back from the robot, acking or NAKing it the same way
+// `send_frame` does in the other direction.
+fn recv_frame(
+    port: &mut Box<dyn SerialPort>,
+    expected_type: u8,
+    retries: u8,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    for attempt in 0..=retries {
+        let mut start = [0; 1];
+        match port.read_exact(&mut start) {
+            Ok(()) if start[0] == FRAME_START => {}
+            // A garbled start byte or a read timeout is the most likely failure on a noisy
+            // link; give it the same retry budget as a CRC or frame-type mismatch below.
+            Ok(()) => {
+                if attempt == retries {
+                    return Err(format!(
+                        "Unexpected frame start byte 0x{:02X} from robot",
+                        start[0]
+                    )
+                    .into());
+                }
+                continue;
+            }
+            Err(_) if attempt == retries => {
+                return Err(format!("Timed out waiting for frame after {} attempts", retries + 1).into());
+            }
+            Err(_) => continue,
+        }
+
+        let mut header = [0; 3];
+        port.read_exact(&mut header)?;
+        let frame_type = header[0];
+        let len = u16::from_be_bytes([header[1], header[2]]) as usize;
+
+        let mut payload = vec![0u8; len];
+        port.read_exact(&mut payload)?;
+
+        let mut crc_buf = [0; 2];
+        port.read_exact(&mut crc_buf)?;
+        let received_crc = u16::from_be_bytes(crc_buf);
+
+        let mut crc_data = Vec::with_capacity(1 + 2 + payload.len());
+        crc_data.push(frame_type);
+        crc_data.extend_from_slice(&header[1..3]);
+        crc_data.extend_from_slice(&payload);
+
+        if crc16_ccitt_false(&crc_data) != received_crc {
+            port.write_all(&[STATUS_NAK_CRC])?;
+            port.flush()?;
+            if attempt == retries {
+                return Err(format!("CRC mismatch on inbound frame after {} attempts", retries + 1).into());
+            }
+            continue;
+        }
+        if frame_type != expected_type {
+            port.write_all(&[STATUS_NAK_SEQ])?;
+            port.flush()?;
+            if attempt == retries {
+                return Err(format!(
+                    "Expected frame type 0x{:02X} from robot, got 0x{:02X}",
+                    expected_type, frame_type
+                )
+                .into());
+            }
+            continue;
+        }
+
+        port.write_all(&[STATUS_ACK])?;
+        port.flush()?;
+        return Ok(payload);
+    }
+
+    Err("Failed to receive frame from robot".into())
 }
 
 #[repr(C, packed)]
@@ -73,35 +258,43 @@ pub struct ConfigCommand {
     pub vtime: f32,
 }
 
+// One record per executed Command, streamed back after a queued path has run.
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct Telemetry {
+    pub ticks1: i32,
+    pub ticks2: i32,
+    pub heading_error: f32, // radians, measured vs. commanded turn
+    pub elapsed: f32,       // seconds
+}
+
+// Placeholder ConfigCommand used wherever a calibration/self-test path needs *some* config on
+// the wire but isn't tuning velocity/time itself; shared so self_test and the autotuner can't
+// drift apart on what "placeholder" means.
+pub fn default_config_command(config: &Config) -> ConfigCommand {
+    ConfigCommand {
+        kp_turn: config.kp_move,
+        kp_hold: config.kp_hold,
+        kp_straight: config.kp_straight,
+        kp_velocity: config.kp_velocity,
+        dowel_off: config.dowel_off,
+        turn_accel_time: config.turn_accel_time,
+        straight_accel_time: config.straight_accel_time,
+        friction: config.friction,
+        velocity: 10000.0,
+        velocity_twoff: 0.0,
+        time: 10.0,
+        vtime: 0.0,
+    }
+}
+
 pub fn self_test() -> Result<(), Box<dyn std::error::Error>> {
     // Send config
     println!("Transmitting config...");
     let mut port = connect()?;
     let config = read_config()?;
     port.clear(serialport::ClearBuffer::Input)?;
-    let data = unsafe {
-        slice::from_raw_parts(
-            &ConfigCommand {
-                kp_turn: config.kp_turn,
-                kp_hold: config.kp_hold,
-                kp_straight: config.kp_straight,
-                kp_velocity: config.kp_velocity,
-                dowel_off: config.dowel_off,
-                turn_accel_time: config.turn_accel_time,
-                straight_accel_time: config.straight_accel_time,
-                friction: config.friction,
-                velocity: 10000.0,
-                velocity_twoff: 0.0,
-                time: 10.0,
-                vtime: 0.0,
-            } as *const ConfigCommand as *const u8,
-            mem::size_of::<ConfigCommand>(),
-        )
-    };
-    port.write_all(data)?;
-    port.flush()?;
-    port.read_exact(&mut [0; 1])
-        .expect("Failed to read from Serial port"); // Wait for ack
+    send_config(&default_config_command(&config), &mut port, config.retries)?;
 
     // Send command
     port.clear(serialport::ClearBuffer::Input)?;
@@ -113,6 +306,7 @@ pub fn self_test() -> Result<(), Box<dyn std::error::Error>> {
             tw_off: 0.0,
         },
         &mut port,
+        config.retries,
     )?;
 
     println!("Sent self-test command! Turn on battery power, unplug the robot and press the green button to start the self-test.");
@@ -120,33 +314,51 @@ pub fn self_test() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn send_command(command: Command, port: &mut Box<dyn SerialPort>) -> Result<(), std::io::Error> {
+fn send_config(
+    cfg: &ConfigCommand,
+    port: &mut Box<dyn SerialPort>,
+    retries: u8,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data = unsafe {
+        slice::from_raw_parts(cfg as *const ConfigCommand as *const u8, mem::size_of::<ConfigCommand>())
+    };
+    send_frame(port, FRAME_TYPE_CONFIG, data, retries)
+}
+
+fn send_command(
+    command: Command,
+    port: &mut Box<dyn SerialPort>,
+    retries: u8,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let command_type = command.command_type;
     let data = unsafe {
         slice::from_raw_parts(
             &command as *const Command as *const u8,
             mem::size_of::<Command>(),
         )
     };
-    port.write_all(data)?;
-    port.flush()?;
+    send_frame(port, command_type, data, retries)
+}
 
-    Ok(())
+pub fn transmit(
+    cfg: ConfigCommand,
+    moves: Vec<Command>,
+    retries: u8,
+) -> Result<Vec<Telemetry>, Box<dyn std::error::Error>> {
+    let mut port = connect()?;
+    transmit_on(&mut port, cfg, moves, retries)
 }
 
-pub fn transmit(cfg: ConfigCommand, moves: Vec<Command>) -> Result<(), Box<dyn std::error::Error>> {
+// Same as `transmit`, but reuses an already-open port instead of reconnecting.
+pub fn transmit_on(
+    port: &mut Box<dyn SerialPort>,
+    cfg: ConfigCommand,
+    moves: Vec<Command>,
+    retries: u8,
+) -> Result<Vec<Telemetry>, Box<dyn std::error::Error>> {
     // Write config
-    let mut port = connect()?;
     port.clear(serialport::ClearBuffer::Input)?;
-    let data = unsafe {
-        slice::from_raw_parts(
-            &cfg as *const ConfigCommand as *const u8,
-            mem::size_of::<ConfigCommand>(),
-        )
-    };
-    port.write_all(data)?;
-    port.flush()?;
-    port.read_exact(&mut [0; 1])
-        .expect("Failed to read from Serial port"); // Wait for ack
+    send_config(&cfg, port, retries)?;
 
     // Transmit moves
     send_command(
@@ -156,13 +368,110 @@ pub fn transmit(cfg: ConfigCommand, moves: Vec<Command>) -> Result<(), Box<dyn s
             ticks: moves.len() as i32,
             tw_off: 0.0,
         },
+        port,
+        retries,
+    )?;
+    for m in moves.iter().cloned() {
+        send_command(m, port, retries)?;
+    }
+
+    // Once the queued path has executed, read back one telemetry record per Command.
+    send_command(
+        Command {
+            command_type: CommandType::ReadTelemetry as u8,
+            turn: 0.0,
+            ticks: moves.len() as i32,
+            tw_off: 0.0,
+        },
+        port,
+        retries,
+    )?;
+    let mut telemetry = Vec::with_capacity(moves.len());
+    for _ in 0..moves.len() {
+        let payload = recv_frame(port, FRAME_TYPE_TELEMETRY, retries)?;
+        if payload.len() != mem::size_of::<Telemetry>() {
+            return Err("Telemetry record had the wrong size".into());
+        }
+        telemetry.push(unsafe { std::ptr::read_unaligned(payload.as_ptr() as *const Telemetry) });
+    }
+
+    Ok(telemetry)
+}
+
+pub fn read_config_from_robot(retries: u8) -> Result<ConfigCommand, Box<dyn std::error::Error>> {
+    let mut port = connect()?;
+    port.clear(serialport::ClearBuffer::Input)?;
+    send_command(
+        Command {
+            command_type: CommandType::ReadConfig as u8,
+            turn: 0.0,
+            ticks: 0,
+            tw_off: 0.0,
+        },
+        &mut port,
+        retries,
+    )?;
+
+    let payload = recv_frame(&mut port, FRAME_TYPE_CONFIG, retries)?;
+    if payload.len() != mem::size_of::<ConfigCommand>() {
+        return Err("Robot config had the wrong size".into());
+    }
+
+    Ok(unsafe { std::ptr::read_unaligned(payload.as_ptr() as *const ConfigCommand) })
+}
+
+pub fn flash(path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    let image = std::fs::read(&path)?;
+    let config = read_config()?;
+
+    let mut port = connect()?;
+    port.clear(serialport::ClearBuffer::Input)?;
+
+    // Ask the firmware to drop into the bootloader.
+    println!("Entering update mode...");
+    send_command(
+        Command {
+            command_type: CommandType::Flash as u8,
+            turn: 0.0,
+            ticks: 0,
+            tw_off: 0.0,
+        },
         &mut port,
+        config.retries,
     )?;
-    for m in moves {
-        port.read_exact(&mut [0; 1])
-            .expect("Failed to read from Serial port"); // Wait for ack
-        send_command(m, &mut port)?;
+
+    // Erase the target region. This can take much longer than a normal ack.
+    println!("Erasing...");
+    let prev_timeout = port.timeout();
+    port.set_timeout(FLASH_ERASE_TIMEOUT)?;
+    let erase_result = send_frame(&mut port, FRAME_TYPE_FLASH_ERASE, &[], config.retries);
+    port.set_timeout(prev_timeout)?;
+    erase_result?;
+
+    // Stream the image in fixed-size blocks, each tagged with a monotonically increasing
+    // sequence number, waiting for a per-block ack before sending the next.
+    let total_blocks = image.len().div_ceil(FLASH_BLOCK_SIZE);
+    for (seq, chunk) in image.chunks(FLASH_BLOCK_SIZE).enumerate() {
+        let mut payload = Vec::with_capacity(4 + chunk.len());
+        payload.extend_from_slice(&(seq as u32).to_be_bytes());
+        payload.extend_from_slice(chunk);
+        send_frame(&mut port, FRAME_TYPE_FLASH_BLOCK, &payload, config.retries)?;
+        println!("Sent block {}/{}", seq + 1, total_blocks);
     }
 
+    // Finalize with the total length and a CRC-32 of the whole image so the robot can verify it.
+    println!("Finalizing...");
+    let mut finalize_payload = Vec::with_capacity(8);
+    finalize_payload.extend_from_slice(&(image.len() as u32).to_be_bytes());
+    finalize_payload.extend_from_slice(&crc32(&image).to_be_bytes());
+    send_frame(
+        &mut port,
+        FRAME_TYPE_FLASH_FINALIZE,
+        &finalize_payload,
+        config.retries,
+    )?;
+
+    println!("Flashed {} bytes to Tektite-R.", image.len());
+
     Ok(())
 }
@@ -2,13 +2,20 @@ use std::path::PathBuf;
 
 use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
 use config::config_command;
+use config::pull_command;
 pub use config::Config;
 
 mod connection;
 pub use config::read_config;
 use connection::transmit;
-pub use connection::{Command, CommandType, ConfigCommand};
+pub use connection::{Command, CommandType, ConfigCommand, Telemetry};
 mod planner;
+mod runlog;
+use runlog::write_run_log;
+mod simulate;
+use simulate::simulate;
+mod tune;
+use tune::tune_command;
 
 #[derive(Parser)]
 #[command(version, about)]
@@ -59,17 +66,49 @@ enum Commands {
 
         #[arg(long)]
         imu_weight: Option<f32>,
+
+        #[arg(long)]
+        retries: Option<u8>,
     },
     #[command(about = "Run a self-test on the robot.")]
     SelfTest,
     Run {
         file: PathBuf,
     },
+    #[command(about = "Flash new firmware onto Tektite-R over the existing serial connection.")]
+    Flash {
+        file: PathBuf,
+    },
+    #[command(about = "Pull the robot's live config and diff it against the local config.")]
+    Pull {
+        #[arg(long)]
+        write: bool,
+    },
+    #[command(about = "Simulate a path offline and inspect the resulting trajectory.")]
+    Simulate {
+        file: PathBuf,
+
+        #[arg(long)]
+        csv: Option<PathBuf>,
+
+        #[arg(long)]
+        svg: Option<PathBuf>,
+
+        #[arg(long)]
+        interactive: bool,
+    },
+    #[command(
+        about = "Autotune kp_straight, kp_move, and kp_hold against a short calibration path."
+    )]
+    Tune,
 }
 
 fn run_path(path: PathBuf, config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    let retries = config.retries;
     let res = planner::plan(path, config)?;
-    transmit(res.config, res.commands)?;
+    let telemetry = transmit(res.config, res.commands.clone(), retries)?;
+    let log_path = write_run_log(&res.commands, &telemetry)?;
+    println!("Run log written to {}", log_path.display());
     Ok(())
 }
 
@@ -97,6 +136,7 @@ fn main() {
             reverse_enc,
             reverse_enc2,
             imu_weight,
+            retries,
         } => config_command(
             ticks_per_cm,
             kp_move,
@@ -111,9 +151,19 @@ fn main() {
             reverse_enc,
             reverse_enc2,
             imu_weight,
+            retries,
         ),
         Commands::SelfTest => connection::self_test(),
         Commands::Run { file } => run_path(file, config),
+        Commands::Flash { file } => connection::flash(file),
+        Commands::Pull { write } => pull_command(write),
+        Commands::Simulate {
+            file,
+            csv,
+            svg,
+            interactive,
+        } => simulate(file, config, csv, svg, interactive),
+        Commands::Tune => tune_command(),
     } {
         Cli::command().error(ErrorKind::Io, v.to_string()).exit();
     }
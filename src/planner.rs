@@ -1,11 +1,13 @@
 use super::{Command, CommandType, Config, ConfigCommand};
 use std::{f32::consts::PI, io::BufRead, path::PathBuf};
 const EPSILON: f32 = 1e-4;
-const CM_PER_SQUARE: f32 = 50.0;
+// Also used by the trajectory simulator to draw paths at the same scale.
+pub(crate) const CM_PER_SQUARE: f32 = 50.0;
 
 pub struct PlanningResult {
     pub commands: Vec<Command>,
     pub config: ConfigCommand,
+    pub initial_heading: f32,
 }
 
 #[derive(Debug)]
@@ -120,6 +122,7 @@ pub fn plan(path: PathBuf, config: Config) -> Result<PlanningResult, Box<dyn std
     }
 
     let mut angle = tokens[0].target_angle(); // 0 is pointing east
+    let initial_heading = angle;
     for tok in tokens.iter() {
         commands.push(plan_token(tok, &mut angle, &config));
     }
@@ -203,8 +206,9 @@ pub fn plan(path: PathBuf, config: Config) -> Result<PlanningResult, Box<dyn std
 
     Ok(PlanningResult {
         commands,
+        initial_heading,
         config: ConfigCommand {
-            kp_turn: config.kp_turn,
+            kp_turn: config.kp_move,
             kp_hold: config.kp_hold,
             kp_straight: config.kp_straight,
             kp_velocity: config.kp_velocity,
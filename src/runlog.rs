@@ -0,0 +1,81 @@
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{Command, Telemetry};
+
+#[derive(Serialize)]
+struct RunSummary {
+    timestamp: u64,
+    moves: usize,
+    avg_heading_error_deg: f32,
+    max_heading_error_deg: f32,
+    total_elapsed: f32,
+}
+
+// Write a timestamped run-log (CSV of target-vs-actual per move, plus a summary TOML) and
+// print the same table.
+pub fn write_run_log(
+    commands: &[Command],
+    telemetry: &[Telemetry],
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let log_dir = dirs::config_dir()
+        .ok_or("Failed to get config directory")?
+        .join("rotour/runs");
+    fs::create_dir_all(&log_dir)?;
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let csv_path = log_dir.join(format!("{}.csv", timestamp));
+    let toml_path = log_dir.join(format!("{}.toml", timestamp));
+
+    let mut csv = File::create(&csv_path)?;
+    writeln!(csv, "index,target_ticks,ticks1,ticks2,heading_error_deg,elapsed")?;
+
+    println!(
+        "{:>5} {:>12} {:>10} {:>10} {:>14} {:>8}",
+        "move", "target", "ticks1", "ticks2", "heading err", "elapsed"
+    );
+
+    let mut total_elapsed = 0.0;
+    let mut total_abs_error = 0.0;
+    let mut max_abs_error: f32 = 0.0;
+    for (i, (cmd, t)) in commands.iter().zip(telemetry.iter()).enumerate() {
+        let target_ticks = cmd.ticks;
+        let ticks1 = t.ticks1;
+        let ticks2 = t.ticks2;
+        let elapsed = t.elapsed;
+        let heading_error_deg = t.heading_error.to_degrees();
+
+        writeln!(
+            csv,
+            "{},{},{},{},{:.4},{:.4}",
+            i, target_ticks, ticks1, ticks2, heading_error_deg, elapsed
+        )?;
+        println!(
+            "{:>5} {:>12} {:>10} {:>10} {:>13.2}d {:>7.2}s",
+            i, target_ticks, ticks1, ticks2, heading_error_deg, elapsed
+        );
+
+        total_elapsed += elapsed;
+        let abs_error = t.heading_error.abs();
+        total_abs_error += abs_error;
+        max_abs_error = max_abs_error.max(abs_error);
+    }
+
+    let summary = RunSummary {
+        timestamp,
+        moves: commands.len(),
+        avg_heading_error_deg: if commands.is_empty() {
+            0.0
+        } else {
+            (total_abs_error / commands.len() as f32).to_degrees()
+        },
+        max_heading_error_deg: max_abs_error.to_degrees(),
+        total_elapsed,
+    };
+    fs::write(&toml_path, toml::to_string(&summary)?)?;
+
+    Ok(csv_path)
+}
@@ -0,0 +1,389 @@
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use super::{planner, Command, Config};
+use planner::CM_PER_SQUARE;
+
+const EPSILON: f32 = 1e-4;
+const SAMPLES_PER_MOVE: usize = 16;
+
+#[derive(Clone, Copy)]
+pub struct TrajectoryPoint {
+    pub t: f32,
+    pub x: f32,
+    pub y: f32,
+    pub heading: f32,
+    pub command_index: usize,
+}
+
+// Snapshot of the robot's state after a planned Command has fully executed.
+#[derive(Clone, Copy)]
+pub struct CommandState {
+    pub index: usize,
+    pub heading: f32,
+    pub x: f32,
+    pub y: f32,
+    pub tw_off: f32,
+}
+
+pub struct SimulationResult {
+    pub trajectory: Vec<TrajectoryPoint>,
+    pub states: Vec<CommandState>,
+}
+
+// Fixed ramp time; falls back to a triangular profile if `distance` can't reach `cruise` in
+// time. Returns (peak velocity, cruise duration).
+fn trapezoid(distance: f32, cruise: f32, accel_time: f32) -> (f32, f32) {
+    if accel_time <= 0.0 || distance.abs() < EPSILON {
+        return (0.0, 0.0);
+    }
+    let ramp_distance = cruise.abs() * accel_time;
+    if cruise.abs() < EPSILON || ramp_distance >= distance.abs() {
+        (distance.abs() / accel_time, 0.0)
+    } else {
+        (cruise.abs(), (distance.abs() - ramp_distance) / cruise.abs())
+    }
+}
+
+// Distance covered `elapsed` seconds into a trapezoidal move with the given ramp parameters.
+fn trapezoid_progress(elapsed: f32, peak: f32, accel_time: f32, cruise_time: f32) -> f32 {
+    if accel_time <= 0.0 {
+        return 0.0;
+    }
+    if elapsed <= accel_time {
+        0.5 * peak / accel_time * elapsed * elapsed
+    } else if elapsed <= accel_time + cruise_time {
+        0.5 * peak * accel_time + peak * (elapsed - accel_time)
+    } else {
+        let t = elapsed - accel_time - cruise_time;
+        0.5 * peak * accel_time + peak * cruise_time + peak * t - 0.5 * peak / accel_time * t * t
+    }
+}
+
+// Integrate one command's turn-then-move into trajectory samples, returning the new `(t, x, y, heading)`.
+fn integrate_command(
+    index: usize,
+    command: &Command,
+    ticks_per_cm: f32,
+    turn_accel_time: f32,
+    straight_accel_time: f32,
+    cruise_cm_s: f32,
+    t: f32,
+    x: f32,
+    y: f32,
+    heading: f32,
+    trajectory: &mut Vec<TrajectoryPoint>,
+) -> (f32, f32, f32, f32) {
+    let turn = command.turn;
+    let ticks = command.ticks;
+
+    // Turn phase: heading ramps from `heading` to `heading + turn`, position is unchanged.
+    let (peak_w, w_cruise_time) = trapezoid(turn, 0.0, turn_accel_time);
+    let turn_duration = if peak_w.abs() < EPSILON {
+        0.0
+    } else {
+        2.0 * turn_accel_time + w_cruise_time
+    };
+    let turn_sign = turn.signum();
+    for i in 1..=SAMPLES_PER_MOVE {
+        let elapsed = turn_duration * i as f32 / SAMPLES_PER_MOVE as f32;
+        let progress = turn_sign * trapezoid_progress(elapsed, peak_w, turn_accel_time, w_cruise_time);
+        trajectory.push(TrajectoryPoint {
+            t: t + elapsed,
+            x,
+            y,
+            heading: heading + progress,
+            command_index: index,
+        });
+    }
+    let t = t + turn_duration;
+    let heading = heading + turn;
+
+    // Move phase: straight-line distance along the new heading.
+    let dist_cm = ticks as f32 / ticks_per_cm;
+    let (peak_v, v_cruise_time) = trapezoid(dist_cm, cruise_cm_s, straight_accel_time);
+    let move_duration = if peak_v.abs() < EPSILON {
+        0.0
+    } else {
+        2.0 * straight_accel_time + v_cruise_time
+    };
+    let move_sign = dist_cm.signum();
+    for i in 1..=SAMPLES_PER_MOVE {
+        let elapsed = move_duration * i as f32 / SAMPLES_PER_MOVE as f32;
+        let progress =
+            move_sign * trapezoid_progress(elapsed, peak_v, straight_accel_time, v_cruise_time);
+        trajectory.push(TrajectoryPoint {
+            t: t + elapsed,
+            x: x + heading.cos() * progress,
+            y: y + heading.sin() * progress,
+            heading,
+            command_index: index,
+        });
+    }
+    let t = t + move_duration;
+    let total_dist = move_sign * dist_cm.abs();
+    let x = x + heading.cos() * total_dist;
+    let y = y + heading.sin() * total_dist;
+
+    (t, x, y, heading)
+}
+
+pub fn build_trajectory(
+    commands: &[Command],
+    initial_heading: f32,
+    ticks_per_cm: f32,
+    turn_accel_time: f32,
+    straight_accel_time: f32,
+    cruise_cm_s: f32,
+) -> SimulationResult {
+    let mut trajectory = Vec::new();
+    let mut states = Vec::with_capacity(commands.len());
+
+    trajectory.push(TrajectoryPoint {
+        t: 0.0,
+        x: 0.0,
+        y: 0.0,
+        heading: initial_heading,
+        command_index: 0,
+    });
+
+    let (mut t, mut x, mut y, mut heading) = (0.0, 0.0, 0.0, initial_heading);
+    for (index, command) in commands.iter().enumerate() {
+        let result = integrate_command(
+            index,
+            command,
+            ticks_per_cm,
+            turn_accel_time,
+            straight_accel_time,
+            cruise_cm_s,
+            t,
+            x,
+            y,
+            heading,
+            &mut trajectory,
+        );
+        t = result.0;
+        x = result.1;
+        y = result.2;
+        heading = result.3;
+
+        states.push(CommandState {
+            index,
+            heading,
+            x,
+            y,
+            tw_off: command.tw_off,
+        });
+    }
+
+    SimulationResult { trajectory, states }
+}
+
+pub fn write_csv(path: &PathBuf, trajectory: &[TrajectoryPoint]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = File::create(path)?;
+    writeln!(file, "t,x,y,heading_deg,command_index")?;
+    for p in trajectory {
+        writeln!(
+            file,
+            "{:.4},{:.4},{:.4},{:.4},{}",
+            p.t,
+            p.x,
+            p.y,
+            p.heading.to_degrees(),
+            p.command_index
+        )?;
+    }
+    Ok(())
+}
+
+pub fn write_svg(path: &PathBuf, trajectory: &[TrajectoryPoint]) -> Result<(), Box<dyn std::error::Error>> {
+    let margin = CM_PER_SQUARE;
+    let min_x = trajectory.iter().fold(f32::INFINITY, |m, p| m.min(p.x)) - margin;
+    let max_x = trajectory.iter().fold(f32::NEG_INFINITY, |m, p| m.max(p.x)) + margin;
+    let min_y = trajectory.iter().fold(f32::INFINITY, |m, p| m.min(p.y)) - margin;
+    let max_y = trajectory.iter().fold(f32::NEG_INFINITY, |m, p| m.max(p.y)) + margin;
+    let width = max_x - min_x;
+    let height = max_y - min_y;
+
+    let mut file = File::create(path)?;
+    writeln!(
+        file,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
+        min_x, -max_y, width, height
+    )?;
+    write!(file, "<polyline fill=\"none\" stroke=\"black\" stroke-width=\"1\" points=\"")?;
+    for p in trajectory {
+        // SVG y grows downward; flip so "up" on the grid is up on the page.
+        write!(file, "{:.2},{:.2} ", p.x, -p.y)?;
+    }
+    writeln!(file, "\" />")?;
+    writeln!(file, "</svg>")?;
+    Ok(())
+}
+
+fn print_state(state: &CommandState) {
+    println!(
+        "command {}: heading={:.2}deg pos=({:.2}, {:.2}) tw_off={:.3}",
+        state.index,
+        state.heading.to_degrees(),
+        state.x,
+        state.y,
+        state.tw_off
+    );
+}
+
+// Distance from `heading` to the nearest grid-aligned cardinal direction (0/90/180/270 deg).
+fn cardinal_error(heading: f32) -> f32 {
+    let step = PI / 2.0;
+    let rem = ((heading % step) + step) % step;
+    rem.min(step - rem)
+}
+
+// Step through planned Commands one at a time, with breakpoints on index or heading error.
+pub fn run_stepper(commands: &[Command], states: &[CommandState], initial_heading: f32) {
+    println!("Interactive stepper. Commands: n(ext), c(ontinue), b <index>, e <deg>, d(ump), q(uit), h(elp)");
+
+    let mut index = 0usize;
+    let mut index_breakpoint: Option<usize> = None;
+    let mut heading_error_threshold: Option<f32> = None;
+    let mut prev_heading = initial_heading;
+
+    let stdin = io::stdin();
+    loop {
+        if index >= states.len() {
+            println!("End of plan ({} commands).", states.len());
+            return;
+        }
+
+        print!("(step {}/{}) > ", index, states.len());
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            return; // stdin closed
+        }
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            None | Some("n") | Some("next") => {
+                step(commands, states, &mut index, &mut prev_heading);
+            }
+            Some("c") | Some("continue") => loop {
+                if index >= states.len() {
+                    println!("End of plan ({} commands).", states.len());
+                    break;
+                }
+                if Some(index) == index_breakpoint {
+                    println!("Breakpoint hit at command {}.", index);
+                    break;
+                }
+                step(commands, states, &mut index, &mut prev_heading);
+                if let Some(threshold) = heading_error_threshold {
+                    let error = cardinal_error(states[index - 1].heading);
+                    if error > threshold {
+                        println!(
+                            "Heading-error breakpoint hit at command {} ({:.2}deg off grid > {:.2}deg).",
+                            index - 1,
+                            error.to_degrees(),
+                            threshold.to_degrees()
+                        );
+                        break;
+                    }
+                }
+            },
+            Some("b") => {
+                if let Some(idx) = parts.next().and_then(|s| s.parse::<usize>().ok()) {
+                    index_breakpoint = Some(idx);
+                    println!("Breakpoint set at command {}.", idx);
+                } else {
+                    println!("Usage: b <command index>");
+                }
+            }
+            Some("e") => {
+                if let Some(deg) = parts.next().and_then(|s| s.parse::<f32>().ok()) {
+                    heading_error_threshold = Some(deg.to_radians());
+                    println!("Heading-error breakpoint set at {:.2} degrees.", deg);
+                } else {
+                    println!("Usage: e <degrees>");
+                }
+            }
+            Some("d") | Some("dump") => {
+                if index > 0 {
+                    print_state(&states[index - 1]);
+                } else {
+                    println!("heading={:.2}deg pos=(0.00, 0.00)", initial_heading.to_degrees());
+                }
+            }
+            Some("q") | Some("quit") => return,
+            Some("h") | Some("help") => {
+                println!("n(ext)              step one command");
+                println!("c(ontinue)          run until a breakpoint");
+                println!("b <index>           set a command-index breakpoint");
+                println!("e <deg>             set a heading-error breakpoint");
+                println!("d(ump)              print the current state");
+                println!("q(uit)              exit the stepper");
+            }
+            _ => println!("Unknown command. Type 'h' for help."),
+        }
+    }
+}
+
+fn step(commands: &[Command], states: &[CommandState], index: &mut usize, prev_heading: &mut f32) {
+    let turn = commands[*index].turn;
+    let state = &states[*index];
+    println!(
+        "Turn: {:.2}deg -> command {}: heading={:.2}deg pos=({:.2}, {:.2}) tw_off={:.3}",
+        turn.to_degrees(),
+        *index,
+        state.heading.to_degrees(),
+        state.x,
+        state.y,
+        state.tw_off
+    );
+    *prev_heading = state.heading;
+    *index += 1;
+}
+
+pub fn simulate(
+    path: PathBuf,
+    config: Config,
+    csv: Option<PathBuf>,
+    svg: Option<PathBuf>,
+    interactive: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let ticks_per_cm = config.ticks_per_cm;
+    let turn_accel_time = config.turn_accel_time;
+    let straight_accel_time = config.straight_accel_time;
+
+    let res = planner::plan(path, config)?;
+    let cruise_cm_s = if res.config.vtime > EPSILON {
+        (res.config.velocity / res.config.vtime) / ticks_per_cm
+    } else {
+        0.0
+    };
+
+    let result = build_trajectory(
+        &res.commands,
+        res.initial_heading,
+        ticks_per_cm,
+        turn_accel_time,
+        straight_accel_time,
+        cruise_cm_s,
+    );
+
+    if let Some(csv_path) = csv {
+        write_csv(&csv_path, &result.trajectory)?;
+        println!("Wrote trajectory CSV to {}", csv_path.display());
+    }
+    if let Some(svg_path) = svg {
+        write_svg(&svg_path, &result.trajectory)?;
+        println!("Wrote trajectory SVG to {}", svg_path.display());
+    }
+
+    if interactive {
+        run_stepper(&res.commands, &result.states, res.initial_heading);
+    }
+
+    Ok(())
+}
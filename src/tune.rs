@@ -0,0 +1,158 @@
+use std::f32::consts::PI;
+
+use serialport::SerialPort;
+
+use super::{Command, CommandType, Telemetry};
+use crate::config::{read_config, save_config};
+use crate::connection::{connect, default_config_command, transmit_on};
+use crate::Config;
+
+const CAL_DISTANCE_CM: f32 = 50.0;
+const MAX_ITERATIONS: u32 = 8;
+
+const TICKS_TOLERANCE: f32 = 20.0;
+const HEADING_TOLERANCE: f32 = 0.02; // radians
+
+// Per-gain clamps, scaled to each gain's own operating range rather than one shared bound.
+const KP_STRAIGHT_MIN: f32 = 0.1;
+const KP_STRAIGHT_MAX: f32 = 20.0;
+const KP_MOVE_MIN: f32 = 0.1;
+const KP_MOVE_MAX: f32 = 20.0;
+const KP_HOLD_MIN: f32 = 0.0001;
+const KP_HOLD_MAX: f32 = 0.2;
+
+// Straight segment, 90-degree turn, and a hold to sample steady-state heading error.
+fn calibration_commands(config: &Config) -> (Vec<Command>, i32) {
+    let target_ticks = (CAL_DISTANCE_CM * config.ticks_per_cm) as i32;
+    let commands = vec![
+        Command {
+            command_type: CommandType::TurnMove as u8,
+            turn: 0.0,
+            ticks: target_ticks,
+            tw_off: 0.0,
+        },
+        Command {
+            command_type: CommandType::TurnMove as u8,
+            turn: PI / 2.0,
+            ticks: 0,
+            tw_off: 0.0,
+        },
+        Command {
+            command_type: CommandType::TurnMove as u8,
+            turn: 0.0,
+            ticks: 0,
+            tw_off: 0.0,
+        },
+    ];
+    (commands, target_ticks)
+}
+
+fn run_calibration(
+    port: &mut Box<dyn SerialPort>,
+    config: &Config,
+) -> Result<(Vec<Command>, Vec<Telemetry>), Box<dyn std::error::Error>> {
+    let (commands, _target_ticks) = calibration_commands(config);
+    let telemetry = transmit_on(
+        port,
+        default_config_command(config),
+        commands.clone(),
+        config.retries,
+    )?;
+    Ok((commands, telemetry))
+}
+
+// Relay/step-response search: step `gain` toward lower error, halving the step on overshoot.
+fn tune_gain(
+    mut gain: f32,
+    mut step: f32,
+    min: f32,
+    max: f32,
+    max_iterations: u32,
+    tolerance: f32,
+    mut measure: impl FnMut(f32) -> Result<f32, Box<dyn std::error::Error>>,
+) -> Result<f32, Box<dyn std::error::Error>> {
+    let mut prev_sign = 0.0f32;
+    for iteration in 0..max_iterations {
+        let error = measure(gain)?;
+        println!("  iteration {}: gain={:.5} error={:.5}", iteration, gain, error);
+        if error.abs() < tolerance {
+            break;
+        }
+
+        let sign = error.signum();
+        if prev_sign != 0.0 && sign != prev_sign {
+            step *= 0.5; // overshot the target; refine with a smaller step
+        }
+        gain = (gain + sign * step).clamp(min, max);
+        prev_sign = sign;
+    }
+    Ok(gain)
+}
+
+pub fn tune_command() -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = read_config()?;
+    // Held open across the whole search instead of reconnecting per iteration.
+    let mut port = connect()?;
+
+    println!("Tuning kp_straight...");
+    let initial_step = (config.kp_straight * 0.5).max(0.1);
+    config.kp_straight = tune_gain(
+        config.kp_straight,
+        initial_step,
+        KP_STRAIGHT_MIN,
+        KP_STRAIGHT_MAX,
+        MAX_ITERATIONS,
+        TICKS_TOLERANCE,
+        |gain| {
+            let mut trial = config.clone();
+            trial.kp_straight = gain;
+            let (commands, telemetry) = run_calibration(&mut port, &trial)?;
+            let actual = (telemetry[0].ticks1 + telemetry[0].ticks2) as f32 / 2.0;
+            Ok(actual - commands[0].ticks as f32)
+        },
+    )?;
+    save_config(&config)?;
+
+    println!("Tuning kp_move...");
+    let initial_step = (config.kp_move * 0.5).max(0.1);
+    config.kp_move = tune_gain(
+        config.kp_move,
+        initial_step,
+        KP_MOVE_MIN,
+        KP_MOVE_MAX,
+        MAX_ITERATIONS,
+        HEADING_TOLERANCE,
+        |gain| {
+            let mut trial = config.clone();
+            trial.kp_move = gain;
+            let (_commands, telemetry) = run_calibration(&mut port, &trial)?;
+            Ok(telemetry[1].heading_error)
+        },
+    )?;
+    save_config(&config)?;
+
+    println!("Tuning kp_hold...");
+    let initial_step = (config.kp_hold * 0.5).max(0.001);
+    config.kp_hold = tune_gain(
+        config.kp_hold,
+        initial_step,
+        KP_HOLD_MIN,
+        KP_HOLD_MAX,
+        MAX_ITERATIONS,
+        HEADING_TOLERANCE,
+        |gain| {
+            let mut trial = config.clone();
+            trial.kp_hold = gain;
+            let (_commands, telemetry) = run_calibration(&mut port, &trial)?;
+            Ok(telemetry[2].heading_error)
+        },
+    )?;
+    save_config(&config)?;
+
+    println!(
+        "Converged: kp_straight={} kp_move={} kp_hold={}",
+        config.kp_straight, config.kp_move, config.kp_hold
+    );
+
+    Ok(())
+}